@@ -1,10 +1,11 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use anyhow::{Result, Context};
 use serde::{Serialize, Deserialize};
 use std::fs::{self, File};
-use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use walkdir::WalkDir;
 
 const JSON_FILE: &str = ".git_projects.json";
@@ -13,10 +14,77 @@ const JSON_FILE: &str = ".git_projects.json";
 #[command(name = "pm-tool")]
 #[command(about = "批量管理当前目录下所有 git 项目（scan/clone/grep/pull）", long_about = None)]
 struct Cli {
+    /// 并行执行的 worker 数量，默认为 CPU 核心数
+    #[arg(long, short, global = true, default_value_t = default_jobs())]
+    jobs: usize,
+    /// 根目录：clone 的目标目录，也是 scan/grep/pull 查找仓库的目录；优先级高于环境变量 PM_TOOL_PATH
+    #[arg(long, global = true)]
+    root: Option<PathBuf>,
+    /// 递归处理子模块（clone 加 --recursive，clone/pull 后更新子模块）
+    #[arg(long, global = true)]
+    recursive: bool,
+    /// 输出格式：text（默认，逐仓库打印并附汇总）或 json
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Text => f.write_str("text"),
+            Format::Json => f.write_str("json"),
+        }
+    }
+}
+
+fn default_jobs() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// 简单的 worker 池：用 `jobs` 个线程并发处理 `items`，每个任务的结果按原始
+/// 顺序回填，保证不同仓库的输出不会交错。
+fn run_parallel<T, R, F>(items: Vec<T>, jobs: usize, f: F) -> Vec<R>
+where
+    T: Send + Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.max(1);
+    let next = AtomicUsize::new(0);
+    let slots: Vec<Mutex<Option<R>>> = (0..items.len()).map(|_| Mutex::new(None)).collect();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= items.len() {
+                    break;
+                }
+                // `thread::scope` 会在任一子线程 panic 后自身重新 panic 并中止整批，
+                // 所以必须在这里用 catch_unwind 把单个仓库的 panic 挡在 worker 内部，
+                // 对应 slot 保持 None，而不是让它向上传播。
+                let item = &items[i];
+                let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(item))).ok();
+                *slots[i].lock().unwrap() = r;
+            });
+        }
+    });
+    // 某个仓库 panic 时对应 slot 为 None，用 filter_map 跳过它，不影响其它仓库的结果。
+    slots
+        .into_iter()
+        .filter_map(|m| m.into_inner().unwrap())
+        .collect()
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// 扫描所有 git 项目并生成 json
@@ -35,22 +103,132 @@ enum Commands {
 #[derive(Serialize, Deserialize)]
 struct RepoRemote {
     remote: String,
+    /// 记录仓库所在的分支；与 `revision` 互斥。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    branch: Option<String>,
+    /// 记录固定的提交 sha；与 `branch` 互斥。
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    revision: Option<String>,
+    /// 仓库是否包含子模块（存在 `.gitmodules` 文件）。
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    submodules: bool,
+}
+
+impl RepoRemote {
+    /// branch 与 revision 互斥，不能同时出现在同一条清单记录中。
+    fn validate(&self) -> Result<()> {
+        if self.branch.is_some() && self.revision.is_some() {
+            return Err(anyhow::anyhow!(
+                "清单记录 {} 同时指定了 branch 和 revision，二者互斥",
+                self.remote
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// 单个仓库上一条（或一组）git 命令的执行结果。各子命令为每个仓库收集一个
+/// `RepoResult`，而不是用 `?` 在第一个失败处中断整批操作。
+#[derive(Serialize)]
+struct RepoResult {
+    path: PathBuf,
+    command: String,
+    /// 该仓库的所有步骤是否都成功。
+    status: bool,
+    stdout: String,
+    stderr: String,
+}
+
+impl RepoResult {
+    fn new(path: &Path, command: impl Into<String>) -> Self {
+        RepoResult {
+            path: path.to_path_buf(),
+            command: command.into(),
+            status: true,
+            stdout: String::new(),
+            stderr: String::new(),
+        }
+    }
+
+    /// 执行一步 git 命令，把输出追加到结果中；失败（spawn 出错或非零退出）
+    /// 会把 `status` 置为 false。返回这一步是否成功，便于调用方提前中止。
+    fn run(&mut self, cmd: Command) -> bool {
+        self.run_accepting(cmd, &[])
+    }
+
+    /// 同 [`run`]，但把 `acceptable` 中列出的退出码也视为成功——例如 `git grep`
+    /// 以退出码 1 表示“无匹配”而非错误，只有 >1 才是真正的失败。
+    fn run_accepting(&mut self, mut cmd: Command, acceptable: &[i32]) -> bool {
+        match cmd.output() {
+            Ok(output) => {
+                self.stdout.push_str(&String::from_utf8_lossy(&output.stdout));
+                self.stderr.push_str(&String::from_utf8_lossy(&output.stderr));
+                let ok = output.status.success()
+                    || output.status.code().is_some_and(|c| acceptable.contains(&c));
+                if !ok {
+                    self.status = false;
+                }
+                ok
+            }
+            Err(e) => {
+                self.stderr.push_str(&format!("{}\n", e));
+                self.status = false;
+                false
+            }
+        }
+    }
+
+    /// 记录一条非子进程来源的错误（如解析清单、创建目录失败）。
+    fn note_error(&mut self, err: impl std::fmt::Display) {
+        self.stderr.push_str(&format!("{}\n", err));
+        self.status = false;
+    }
+}
+
+/// 统一的结果输出：text 逐仓库打印并附成功/失败汇总，json 输出机器可读数组。
+fn report(results: &[RepoResult], format: Format) -> Result<()> {
+    if format == Format::Json {
+        println!("{}", serde_json::to_string_pretty(results)?);
+        return Ok(());
+    }
+
+    for r in results {
+        println!("Processing Git repository in {:?}", r.path);
+        print!("{}", r.stdout);
+        if !r.status {
+            eprint!("{}", r.stderr);
+        }
+    }
+    let failed: Vec<&RepoResult> = results.iter().filter(|r| !r.status).collect();
+    let ok = results.len() - failed.len();
+    println!("== 汇总：{} 个成功，{} 个失败 ==", ok, failed.len());
+    for r in &failed {
+        println!("  失败 {:?}（{}）", r.path, r.command);
+        let stderr = r.stderr.trim();
+        if !stderr.is_empty() {
+            for line in stderr.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
     env_logger::init();
     let cli = Cli::parse();
 
+    let root = resolve_root(cli.root);
     match cli.command {
-        Commands::Scan => scan_git_projects(),
-        Commands::Clone => clone_from_json(),
-        Commands::Grep { pattern } => grep_all_projects(&pattern),
-        Commands::Pull => pull_all_projects(),
+        Commands::Scan => scan_git_projects(&root, cli.jobs, cli.format),
+        Commands::Clone => clone_from_json(root, cli.recursive, cli.format),
+        Commands::Grep { pattern } => grep_all_projects(&pattern, &root, cli.jobs, cli.format),
+        Commands::Pull => pull_all_projects(&root, cli.jobs, cli.recursive, cli.format),
     }
 }
 
-fn find_git_dirs() -> Vec<PathBuf> {
-    WalkDir::new(".")
+fn find_git_dirs(root: &Path) -> Vec<PathBuf> {
+    WalkDir::new(root)
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_dir() && e.file_name() == ".git")
@@ -58,113 +236,424 @@ fn find_git_dirs() -> Vec<PathBuf> {
         .collect()
 }
 
-fn scan_git_projects() -> Result<()> {
+/// 在 `repo_dir` 执行 git 子命令，成功时返回去除首尾空白的 stdout。
+fn git_capture(repo_dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(repo_dir).output().ok()?;
+    if output.status.success() {
+        let s = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if !s.is_empty() {
+            return Some(s);
+        }
+    }
+    None
+}
+
+fn scan_git_projects(root: &Path, jobs: usize, format: Format) -> Result<()> {
+    let scanned = run_parallel(find_git_dirs(root), jobs, |repo_dir| {
+        let mut result = RepoResult::new(repo_dir, "git remote get-url origin");
+        let mut cmd = Command::new("git");
+        cmd.args(["remote", "get-url", "origin"]).current_dir(repo_dir);
+        if !result.run(cmd) {
+            return (None, result);
+        }
+
+        let remote = result.stdout.trim().to_string();
+        if remote.is_empty() {
+            result.note_error("origin 远端为空");
+            return (None, result);
+        }
+
+        // 处于命名分支时记录 branch，处于游离 HEAD 时记录 revision，二者互斥。
+        let head_branch = git_capture(repo_dir, &["rev-parse", "--abbrev-ref", "HEAD"]);
+        let (branch, revision) = match head_branch.as_deref() {
+            Some("HEAD") | None => (None, git_capture(repo_dir, &["rev-parse", "HEAD"])),
+            Some(b) => (Some(b.to_string()), None),
+        };
+        let submodules = repo_dir.join(".gitmodules").exists();
+        (Some(RepoRemote { remote, branch, revision, submodules }), result)
+    });
+
     let mut repos = Vec::new();
-    for repo_dir in find_git_dirs() {
-        let output = Command::new("git")
-            .arg("remote")
-            .arg("get-url")
-            .arg("origin")
-            .current_dir(&repo_dir)
-            .output()
-            .ok();
-
-        if let Some(out) = output {
-            if out.status.success() {
-                let remote = String::from_utf8_lossy(&out.stdout).trim().to_string();
-                if !remote.is_empty() {
-                    repos.push(RepoRemote { remote });
-                }
-            }
+    let mut results = Vec::new();
+    for (repo, result) in scanned {
+        if let Some(repo) = repo {
+            repos.push(repo);
         }
+        results.push(result);
     }
     let file = File::create(JSON_FILE)?;
     serde_json::to_writer_pretty(file, &repos)?;
-    println!("已生成 {}", JSON_FILE);
-    Ok(())
+    if format == Format::Text {
+        println!("已生成 {}", JSON_FILE);
+    }
+    report(&results, format)
+}
+
+/// 解析根目录（clone 的目标目录，也是 scan/grep/pull 查找仓库的目录）：`--root` 优先，其次环境变量 `PM_TOOL_PATH`，最后当前目录。
+fn resolve_root(flag: Option<PathBuf>) -> PathBuf {
+    flag.or_else(|| std::env::var_os("PM_TOOL_PATH").map(PathBuf::from))
+        .unwrap_or_else(|| PathBuf::from("."))
 }
 
-fn clone_from_json() -> Result<()> {
+fn clone_from_json(root: PathBuf, recursive: bool, format: Format) -> Result<()> {
     let data = fs::read_to_string(JSON_FILE)
         .with_context(|| format!("请先执行 scan，未找到 {}", JSON_FILE))?;
     let repos: Vec<RepoRemote> = serde_json::from_str(&data)?;
+    let mut results = Vec::new();
     for repo in repos {
-        let (repo_path, _) = parse_repo_path(&repo.remote)?;
-        if repo_path.exists() {
-            println!("目录 {:?} 已存在，跳过。", repo_path);
-            continue;
-        }
-        if let Some(parent) = repo_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-        println!("正在 clone {} 到 {:?}", repo.remote, repo_path);
-        Command::new("git")
-            .arg("clone")
-            .arg(&repo.remote)
-            .arg(&repo_path)
-            .status()
-            .with_context(|| format!("git clone {} 失败", repo.remote))?;
+        results.push(clone_one(&root, recursive, &repo));
     }
-    Ok(())
+    report(&results, format)
 }
 
-fn parse_repo_path(remote: &str) -> Result<(PathBuf, String)> {
-    if remote.starts_with("git@") {
-        let repo_path = remote
-            .split(':')
-            .nth(1)
-            .and_then(|s| s.strip_suffix(".git"))
-            .ok_or_else(|| anyhow::anyhow!("无法解析仓库路径: {}", remote))?;
-        Ok((PathBuf::from(repo_path), repo_path.to_string()))
-    } else if remote.starts_with("http") {
-        let repo_path = remote
-            .split('/')
-            .skip(3)
-            .collect::<Vec<_>>()
-            .join("/")
-            .strip_suffix(".git")
+/// clone 单个仓库并按记录检出 branch/revision、更新子模块。过程中任何失败都
+/// 记录进 `RepoResult` 而非中断整批。
+fn clone_one(root: &Path, recursive: bool, repo: &RepoRemote) -> RepoResult {
+    let mut result = RepoResult::new(root, format!("git clone {}", repo.remote));
+    if let Err(e) = repo.validate() {
+        result.note_error(e);
+        return result;
+    }
+    let (server, owner, name) = match parse_repo_path(&repo.remote) {
+        Ok(triple) => triple,
+        Err(e) => {
+            result.note_error(e);
+            return result;
+        }
+    };
+    let repo_path = root.join(&server).join(&owner).join(&name);
+    result.path = repo_path.clone();
+    if repo_path.exists() {
+        result.stdout.push_str(&format!("目录 {:?} 已存在，跳过。\n", repo_path));
+        return result;
+    }
+    if let Some(parent) = repo_path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            result.note_error(e);
+            return result;
+        }
+    }
+
+    let mut clone = Command::new("git");
+    clone.arg("clone");
+    if recursive {
+        clone.arg("--recursive");
+    }
+    clone.arg(&repo.remote).arg(&repo_path);
+    if !result.run(clone) {
+        return result;
+    }
+
+    // 优先检出固定的 revision，其次检出记录的 branch。
+    let checkout = repo.revision.as_ref().or(repo.branch.as_ref());
+    if let Some(target) = checkout {
+        let mut cmd = Command::new("git");
+        cmd.arg("checkout").arg(target).current_dir(&repo_path);
+        if !result.run(cmd) {
+            return result;
+        }
+    }
+
+    if recursive {
+        let mut cmd = Command::new("git");
+        cmd.args(["submodule", "update", "--init", "--recursive"]).current_dir(&repo_path);
+        result.run(cmd);
+    }
+    result
+}
+
+/// 把 SSH 或 HTTP(S) 远端拆解为 `(server, owner, repo)` 三元组，供 clone 构造
+/// `<root>/<server>/<owner>/<repo>` 的确定性目录结构，避免不同 host 下同名
+/// owner/repo 相互冲突。
+fn parse_repo_path(remote: &str) -> Result<(String, String, String)> {
+    // 统一成 `host` 与 `owner/.../repo` 两部分。
+    let (host, path) = if let Some(rest) = remote
+        .strip_prefix("ssh://")
+        .or_else(|| remote.strip_prefix("http://"))
+        .or_else(|| remote.strip_prefix("https://"))
+    {
+        // 去掉可能存在的 userinfo（git@）。
+        let rest = rest.rsplit('@').next().unwrap_or(rest);
+        rest.split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("无法解析仓库路径: {}", remote))?
+    } else if let Some(rest) = remote.strip_prefix("git@") {
+        // scp 形式：git@host:owner/repo.git
+        rest.split_once(':')
             .ok_or_else(|| anyhow::anyhow!("无法解析仓库路径: {}", remote))?
-            .to_string();
-        Ok((PathBuf::from(&repo_path), repo_path))
     } else {
-        Err(anyhow::anyhow!("无法解析仓库路径: {}", remote))
+        return Err(anyhow::anyhow!("无法解析仓库路径: {}", remote));
+    };
+
+    // host 可能带端口，例如 host:22，取冒号前部分即可。
+    let server = host.split(':').next().unwrap_or(host).to_string();
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, name) = path
+        .rsplit_once('/')
+        .ok_or_else(|| anyhow::anyhow!("无法解析 owner/repo: {}", remote))?;
+    if server.is_empty() || owner.is_empty() || name.is_empty() {
+        return Err(anyhow::anyhow!("无法解析仓库路径: {}", remote));
     }
+    Ok((server.to_string(), owner.to_string(), name.to_string()))
 }
 
-fn grep_all_projects(pattern: &str) -> Result<()> {
-    for repo_dir in find_git_dirs() {
-        println!("Processing Git repository in {:?}", repo_dir);
-        let output = Command::new("git")
-            .arg("grep")
-            .arg(pattern)
+fn grep_all_projects(pattern: &str, root: &Path, jobs: usize, format: Format) -> Result<()> {
+    let results = run_parallel(find_git_dirs(root), jobs, |repo_dir| {
+        let mut result = RepoResult::new(repo_dir, format!("git grep {}", pattern));
+        let mut cmd = Command::new("git");
+        // 选项必须出现在 pattern 之前，否则 git 会把 pattern 当成第一个非选项参数，
+        // 报 "option '--all-match' must come before non-option arguments"。
+        cmd.arg("grep")
             .arg("--all-match")
             .arg("--break")
             .arg("--heading")
             .arg("--line-number")
-            .arg("--color")
-            .arg("$(git rev-list --all)")
-            .current_dir(&repo_dir)
-            .output()
-            .with_context(|| format!("在 {:?} 执行 grep 出错", repo_dir))?;
-        print!("{}", String::from_utf8_lossy(&output.stdout));
+            // 输出被捕获后会进入 json/文本汇总，必须禁用 ANSI 颜色转义。
+            .arg("--color=never")
+            .arg(pattern)
+            .current_dir(repo_dir);
+        // `$(git rev-list --all)` 不会被 shell 展开，必须自己取出所有 sha 再作为
+        // 参数传入，让 git grep 搜索整个历史；空仓库则退化为搜索工作区。
+        if let Some(revs) = git_capture(repo_dir, &["rev-list", "--all"]) {
+            cmd.args(revs.split_whitespace());
+        }
+        // git grep 退出码 1 表示“无匹配”，不是错误。
+        result.run_accepting(cmd, &[1]);
+        result
+    });
+    report(&results, format)
+}
+
+fn pull_all_projects(root: &Path, jobs: usize, recursive: bool, format: Format) -> Result<()> {
+    let results = run_parallel(find_git_dirs(root), jobs, |repo_dir| pull_one(repo_dir, recursive));
+    report(&results, format)
+}
+
+/// 对单个仓库执行 stash/checkout/pull，所有步骤的输出收集进一个 `RepoResult`，
+/// 任何失败都记录而不中断其它仓库。
+fn pull_one(repo_dir: &Path, recursive: bool) -> RepoResult {
+    let mut result = RepoResult::new(repo_dir, "git pull");
+
+    let mut status = Command::new("git");
+    status.args(["status", "--porcelain"]).current_dir(repo_dir);
+    match status.output() {
+        Ok(out) if !out.stdout.is_empty() => {
+            let mut add = Command::new("git");
+            add.arg("add").arg(".").current_dir(repo_dir);
+            result.run(add);
+            let mut stash = Command::new("git");
+            stash.arg("stash").current_dir(repo_dir);
+            result.run(stash);
+        }
+        Ok(_) => {}
+        Err(e) => {
+            result.note_error(e);
+            return result;
+        }
     }
-    Ok(())
+
+    let remote = match default_remote(repo_dir) {
+        Ok(r) => r,
+        Err(e) => {
+            result.note_error(e);
+            return result;
+        }
+    };
+    let branch = match default_branch(repo_dir, &remote) {
+        Ok(b) => b,
+        Err(e) => {
+            result.note_error(e);
+            return result;
+        }
+    };
+
+    let mut checkout = Command::new("git");
+    checkout.arg("checkout").arg(&branch).current_dir(repo_dir);
+    result.run(checkout);
+
+    let mut pull = Command::new("git");
+    pull.arg("pull").arg(&remote).arg(&branch).current_dir(repo_dir);
+    result.run(pull);
+
+    if recursive {
+        let mut sub = Command::new("git");
+        sub.args(["submodule", "update", "--init", "--recursive"]).current_dir(repo_dir);
+        result.run(sub);
+    }
+    result
 }
 
-fn pull_all_projects() -> Result<()> {
-    for repo_dir in find_git_dirs() {
-        println!("Processing Git repository in {:?}", repo_dir);
-        let status = Command::new("git")
-            .arg("status")
-            .arg("--porcelain")
-            .current_dir(&repo_dir)
-            .output()?;
-        if !status.stdout.is_empty() {
-            Command::new("git").arg("add").arg(".").current_dir(&repo_dir).status()?;
-            Command::new("git").arg("stash").current_dir(&repo_dir).status()?;
+/// 读取 `git config --get-regexp 'remote\..*\.url'`，返回该仓库实际配置的远端名。
+/// 优先 `origin`，否则取第一个配置的远端，从而兼容只有 `upstream` 的 fork。
+fn default_remote(repo_dir: &Path) -> Result<String> {
+    let output = Command::new("git")
+        .args(["config", "--get-regexp", r"remote\..*\.url"])
+        .current_dir(repo_dir)
+        .output()?;
+    let text = String::from_utf8_lossy(&output.stdout);
+    let remotes = parse_configured_remotes(&text);
+    if remotes.iter().any(|r| r == "origin") {
+        return Ok("origin".to_string());
+    }
+    remotes
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("{:?} 未配置任何远端", repo_dir))
+}
+
+/// 解析 `git config --get-regexp 'remote\..*\.url'` 的输出，取出每行形如
+/// `remote.origin.url https://...` 的远端名。
+fn parse_configured_remotes(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            line.split_whitespace()
+                .next()
+                .and_then(|k| k.strip_prefix("remote."))
+                .and_then(|k| k.strip_suffix(".url"))
+                .map(|name| name.to_string())
+        })
+        .collect()
+}
+
+/// 解析仓库在 `remote` 上的默认分支：先尝试
+/// `git symbolic-ref --short refs/remotes/<remote>/HEAD`，失败时回退到解析
+/// `git remote show <remote>` 的 `HEAD branch:` 行。
+fn default_branch(repo_dir: &Path, remote: &str) -> Result<String> {
+    let symref = Command::new("git")
+        .args(["symbolic-ref", "--short"])
+        .arg(format!("refs/remotes/{}/HEAD", remote))
+        .current_dir(repo_dir)
+        .output()?;
+    if symref.status.success() {
+        let value = String::from_utf8_lossy(&symref.stdout);
+        if let Some(branch) = parse_symref_branch(value.trim(), remote) {
+            return Ok(branch.to_string());
         }
-        Command::new("git").arg("checkout").arg("master").current_dir(&repo_dir).status()?;
-        Command::new("git").arg("pull").arg("origin").arg("master").current_dir(&repo_dir).status()?;
     }
-    Ok(())
+
+    let show = Command::new("git")
+        .args(["remote", "show", remote])
+        .current_dir(repo_dir)
+        .output()?;
+    let text = String::from_utf8_lossy(&show.stdout);
+    if let Some(branch) = parse_head_branch_line(&text) {
+        return Ok(branch.to_string());
+    }
+    Err(anyhow::anyhow!("{:?} 无法确定 {} 的默认分支", repo_dir, remote))
+}
+
+/// 从 `git symbolic-ref --short refs/remotes/<remote>/HEAD` 的输出（如
+/// `origin/main`）中剥离 `<remote>/` 前缀，取出分支名。
+fn parse_symref_branch<'a>(value: &'a str, remote: &str) -> Option<&'a str> {
+    let branch = value.strip_prefix(&format!("{}/", remote))?;
+    if branch.is_empty() {
+        None
+    } else {
+        Some(branch)
+    }
+}
+
+/// 从 `git remote show <remote>` 的输出中取出 `HEAD branch: <name>` 一行的分支名。
+fn parse_head_branch_line(text: &str) -> Option<&str> {
+    text.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("HEAD branch:")?;
+        let branch = rest.trim();
+        if branch.is_empty() { None } else { Some(branch) }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_repo_path_https() {
+        let (server, owner, repo) = parse_repo_path("https://github.com/rust-lang/rust.git").unwrap();
+        assert_eq!(server, "github.com");
+        assert_eq!(owner, "rust-lang");
+        assert_eq!(repo, "rust");
+    }
+
+    #[test]
+    fn parse_repo_path_http_without_git_suffix() {
+        let (server, owner, repo) = parse_repo_path("http://gitlab.example.com/group/project").unwrap();
+        assert_eq!(server, "gitlab.example.com");
+        assert_eq!(owner, "group");
+        assert_eq!(repo, "project");
+    }
+
+    #[test]
+    fn parse_repo_path_scp_style() {
+        let (server, owner, repo) = parse_repo_path("git@github.com:owner/repo.git").unwrap();
+        assert_eq!(server, "github.com");
+        assert_eq!(owner, "owner");
+        assert_eq!(repo, "repo");
+    }
+
+    #[test]
+    fn parse_repo_path_ssh_with_userinfo() {
+        let (server, owner, repo) = parse_repo_path("ssh://git@example.com/owner/repo.git").unwrap();
+        assert_eq!(server, "example.com");
+        assert_eq!(owner, "owner");
+        assert_eq!(repo, "repo");
+    }
+
+    #[test]
+    fn parse_repo_path_ssh_with_port() {
+        let (server, owner, repo) = parse_repo_path("ssh://git@example.com:2222/owner/repo.git").unwrap();
+        assert_eq!(server, "example.com");
+        assert_eq!(owner, "owner");
+        assert_eq!(repo, "repo");
+    }
+
+    #[test]
+    fn parse_repo_path_nested_owner_path() {
+        let (server, owner, repo) = parse_repo_path("https://gitlab.com/group/subgroup/project.git").unwrap();
+        assert_eq!(server, "gitlab.com");
+        assert_eq!(owner, "group/subgroup");
+        assert_eq!(repo, "project");
+    }
+
+    #[test]
+    fn parse_repo_path_rejects_unrecognized_scheme() {
+        assert!(parse_repo_path("not-a-remote").is_err());
+    }
+
+    #[test]
+    fn parse_repo_path_rejects_missing_repo() {
+        assert!(parse_repo_path("https://github.com/owner").is_err());
+    }
+
+    #[test]
+    fn parse_configured_remotes_picks_all_names() {
+        let text = "remote.origin.url https://github.com/a/b.git\nremote.upstream.url git@github.com:c/d.git\n";
+        assert_eq!(parse_configured_remotes(text), vec!["origin", "upstream"]);
+    }
+
+    #[test]
+    fn parse_configured_remotes_ignores_unrelated_lines() {
+        let text = "remote.origin.fetch +refs/heads/*:refs/remotes/origin/*\n";
+        assert!(parse_configured_remotes(text).is_empty());
+    }
+
+    #[test]
+    fn parse_symref_branch_strips_remote_prefix() {
+        assert_eq!(parse_symref_branch("origin/main", "origin"), Some("main"));
+    }
+
+    #[test]
+    fn parse_symref_branch_rejects_other_remote() {
+        assert_eq!(parse_symref_branch("upstream/main", "origin"), None);
+    }
+
+    #[test]
+    fn parse_head_branch_line_finds_value() {
+        let text = "* remote origin\n  Fetch URL: https://github.com/a/b.git\n  HEAD branch: main\n  Remote branch:\n    main tracked\n";
+        assert_eq!(parse_head_branch_line(text), Some("main"));
+    }
+
+    #[test]
+    fn parse_head_branch_line_missing() {
+        let text = "* remote origin\n  Fetch URL: https://github.com/a/b.git\n";
+        assert_eq!(parse_head_branch_line(text), None);
+    }
 }